@@ -7,6 +7,7 @@ use httpserver::{HttpContext, Resp, HttpResult};
 use localtime::LocalTime;
 use querystring::querify;
 use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
 
 #[derive(Deserialize)]
 struct PingRequest {
@@ -18,6 +19,8 @@ struct RegRequest {
     endpoint: CompactString,
     path: Option<CompactString>,
     paths: Option<Vec<CompactString>>,
+    /// 健康检查路径，提供后台会定期对该端点发起主动探测
+    health_path: Option<CompactString>,
 }
 
 /// 服务测试，测试服务是否存活
@@ -130,14 +133,14 @@ pub async fn reg(ctx: HttpContext) -> HttpResult {
     }
 
     if let Some(path) = &param.path {
-        if proxy::register_service(path, &param.endpoint) {
+        if proxy::register_service_with_health(path, &param.endpoint, param.health_path.clone()) {
             log::info!("service[{}: {}] registration successful", path, param.endpoint);
         }
     }
 
     if let Some(paths) = &param.paths {
         for path in paths {
-            if proxy::register_service(path, &param.endpoint) {
+            if proxy::register_service_with_health(path, &param.endpoint, param.health_path.clone()) {
                 log::info!("service[{}: {}] registration successful", path, param.endpoint);
             }
         }
@@ -173,6 +176,41 @@ pub async fn unreg(ctx: HttpContext) -> HttpResult {
     Resp::ok_with_empty()
 }
 
+/// 管理接口鉴权：要求请求携带与admin-token配置一致的Authorization头，未配置admin-token时管理接口整体禁用。
+/// 使用常数时间比较，避免逐字节比较的提前返回被用于旁路猜测token
+fn check_admin_token(ctx: &HttpContext) -> bool {
+    let ac = crate::controller::config();
+    if ac.admin_token.is_empty() {
+        return false;
+    }
+    let provided = ctx.req.headers().get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let expected = ac.admin_token.as_bytes();
+    provided.len() == expected.len()
+        && bool::from(provided.as_bytes().ct_eq(expected))
+}
+
+/// 触发优雅停机(需携带管理员token)，效果与收到SIGTERM信号一致
+pub async fn admin_shutdown(ctx: HttpContext) -> HttpResult {
+    if !check_admin_token(&ctx) {
+        return Resp::fail("unauthorized");
+    }
+    log::warn!("收到管理接口的停机请求");
+    crate::controller::get().request_shutdown();
+    Resp::ok_with_empty()
+}
+
+/// 触发配置热重载(需携带管理员token)，效果与收到SIGHUP信号一致
+pub async fn admin_reload(ctx: HttpContext) -> HttpResult {
+    if !check_admin_token(&ctx) {
+        return Resp::fail("unauthorized");
+    }
+    log::warn!("收到管理接口的配置重载请求");
+    crate::controller::get().reload().await;
+    Resp::ok_with_empty()
+}
+
 /// 获取请求中reply参数, 获取优先级: post_data > query_string > url_path > default
 async fn get_reply_param(ctx: HttpContext) -> CompactString {
     let path = CompactString::new(ctx.req.uri().path());
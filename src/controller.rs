@@ -0,0 +1,113 @@
+//! 运行时控制子系统：统一管理优雅停机与配置热重载，使进程无需重启即可运维
+
+use std::sync::{Arc, OnceLock, RwLock};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::AppConf;
+
+/// 进程级控制器，持有关闭的共享取消令牌，以及热重载后对外生效的配置快照
+pub struct Controller {
+    /// 优雅停机信号。使用CancellationToken而非裸Notify：cancel()在任何任务开始poll
+    /// wait_shutdown()之前调用也不会丢失，Notify::notify_waiters()只唤醒当时已在等待的任务，
+    /// 会漏掉在事件循环真正开始轮询停机future之前就到达的SIGTERM/管理员/shutdown请求
+    shutdown: CancellationToken,
+    /// 当前生效的配置快照，reload()原子替换整个Arc，读者clone一份即可，不会与替换过程互相阻塞
+    ac: RwLock<Arc<AppConf>>,
+}
+
+static CONTROLLER: OnceLock<Controller> = OnceLock::new();
+
+/// 接管启动阶段解析出的AppConf，只应在main中调用一次；调用后不应再通过AppConf::get()访问配置，
+/// 一律改为controller::config()，避免热重载与请求路径的并发读写产生数据竞争
+pub fn init(ac: &'static mut AppConf) -> &'static Controller {
+    CONTROLLER.get_or_init(|| Controller {
+        shutdown: CancellationToken::new(),
+        ac: RwLock::new(Arc::new(std::mem::take(ac))),
+    })
+}
+
+/// 获取已初始化的控制器
+pub fn get() -> &'static Controller {
+    CONTROLLER.get().expect("controller::init未被调用")
+}
+
+/// 获取当前生效的配置快照。所有请求路径(proxy_handler/spawn_health_supervisor/apis等)
+/// 都应通过此函数读取配置，而不是直接调用AppConf::get()
+pub fn config() -> Arc<AppConf> {
+    get().ac.read().unwrap().clone()
+}
+
+impl Controller {
+    /// 触发优雅停机：令牌被取消后，不论此前还是之后调用wait_shutdown()都会立即返回
+    pub fn request_shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// 等待优雅停机信号，配合httpserver的停机钩子使用
+    pub async fn wait_shutdown(&self) {
+        self.shutdown.cancelled().await;
+    }
+
+    /// 重新从配置源读取一份完整配置，校验通过后整体替换当前生效的配置快照
+    pub async fn reload(&self) {
+        let mut staged = AppConf::default();
+        match appconfig::reload_conf(&mut staged) {
+            Ok(_) => {
+                if let Ok(level) = asynclog::parse_level(&staged.log_level) {
+                    asynclog::set_level(String::new(), level.to_level_filter());
+                }
+                log::info!(
+                    "配置热重载完成: log-level={} lb-strategy={}",
+                    staged.log_level, staged.lb_strategy,
+                );
+                *self.ac.write().unwrap() = Arc::new(staged);
+            }
+            Err(e) => log::error!("配置热重载失败: {e:#}"),
+        }
+    }
+}
+
+/// 监听SIGTERM/SIGINT触发优雅停机，Unix下额外监听SIGHUP触发配置热重载
+#[cfg(unix)]
+pub fn spawn_signal_listener() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let controller = get();
+    tokio::spawn(async move {
+        let mut sigterm = signal(SignalKind::terminate()).expect("注册SIGTERM监听失败");
+        let mut sigint = signal(SignalKind::interrupt()).expect("注册SIGINT监听失败");
+        let mut sighup = signal(SignalKind::hangup()).expect("注册SIGHUP监听失败");
+
+        loop {
+            tokio::select! {
+                _ = sigterm.recv() => {
+                    log::info!("收到SIGTERM信号，开始优雅停机");
+                    controller.request_shutdown();
+                    break;
+                }
+                _ = sigint.recv() => {
+                    log::info!("收到SIGINT信号，开始优雅停机");
+                    controller.request_shutdown();
+                    break;
+                }
+                _ = sighup.recv() => {
+                    log::info!("收到SIGHUP信号，重新加载配置");
+                    controller.reload().await;
+                }
+            }
+        }
+    });
+}
+
+/// 非Unix平台仅支持Ctrl+C触发优雅停机
+#[cfg(not(unix))]
+pub fn spawn_signal_listener() {
+    let controller = get();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            log::info!("收到Ctrl+C信号，开始优雅停机");
+            controller.request_shutdown();
+        }
+    });
+}
@@ -1,5 +1,7 @@
 mod apis;
+mod controller;
 mod proxy;
+mod proxy_log;
 
 use std::{fmt::Write, time::{SystemTime, Duration}};
 
@@ -36,6 +38,23 @@ appconfig::appconfig_define!(app_conf, AppConf,
     listen      : String => ["l",  "listen",       "Listen",            "服务监听端点 (ip地址:端口号)"],
     conn_timeout: String => ["",   "conn-timeout", "ConnectTimeout",    "连接超时时间(单位: 秒)"],
     gw_path     : String => ["p",  "gw-path",      "GwPath",            "本地服务路径"],
+    heartbeat_ttl        : String => ["", "heartbeat-ttl",         "HeartbeatTtl",        "服务心跳存活时间，超时未刷新则剔除(单位: 秒, 0表示不启用)"],
+    health_check_interval: String => ["", "health-check-interval", "HealthCheckInterval", "存活检测任务执行间隔(单位: 秒)"],
+    health_check_fail_max: String => ["", "health-check-fail-max", "HealthCheckFailMax",  "健康检查连续失败多少次后剔除端点"],
+    lb_strategy : String => ["", "lb-strategy",    "LbStrategy",        "负载均衡策略(round-robin/random/consistent-hash)"],
+    lb_hash_header: String => ["", "lb-hash-header", "LbHashHeader",    "一致性哈希取值的请求头名称，为空时使用客户端地址"],
+    compress_min_size: String => ["", "compress-min-size", "CompressMinSize", "响应体压缩的最小长度阈值(单位: 字节)，仅对带Content-Length的响应生效，无Content-Length的响应不做阈值判断"],
+    compress_types   : String => ["", "compress-types",    "CompressTypes",   "允许压缩的Content-Type前缀列表，逗号分隔"],
+    tls_cert: String => ["", "tls-cert", "TlsCert", "TLS证书文件路径(pem格式)，与tls-key同时配置后启用https监听"],
+    tls_key : String => ["", "tls-key",  "TlsKey",  "TLS私钥文件路径(pem格式)"],
+    access_log    : String => ["", "access-log",     "AccessLog",    "访问日志文件路径，为空时并入主日志输出"],
+    access_log_max: String => ["", "access-log-max", "AccessLogMax", "访问日志文件的最大长度 (单位: k|m|g)"],
+    breaker_fail_threshold: String => ["", "breaker-fail-threshold", "BreakerFailThreshold", "熔断器连续失败多少次后跳闸(Open)"],
+    breaker_window        : String => ["", "breaker-window",         "BreakerWindow",         "熔断失败计数的滑动窗口时长(单位: 秒)"],
+    breaker_cooldown      : String => ["", "breaker-cooldown",       "BreakerCooldown",       "熔断器首次跳闸的冷却时长(单位: 秒)"],
+    breaker_max_cooldown  : String => ["", "breaker-max-cooldown",   "BreakerMaxCooldown",    "熔断器冷却时长指数增长的上限(单位: 秒)"],
+    shutdown_timeout: String => ["", "shutdown-timeout", "ShutdownTimeout", "优雅停机时等待在途请求完成的超时时间(单位: 秒)"],
+    admin_token     : String => ["", "admin-token",      "AdminToken",      "管理接口(shutdown/reload)鉴权token，为空时管理接口禁用"],
 );
 
 impl Default for AppConf {
@@ -50,6 +69,25 @@ impl Default for AppConf {
             listen:       String::from("127.0.0.1:3003"),
             conn_timeout: String::from("3"),
             gw_path:      String::from("/api/gw"),
+            heartbeat_ttl:         String::from("30"),
+            health_check_interval: String::from("10"),
+            health_check_fail_max: String::from("3"),
+            lb_strategy:    String::from("round-robin"),
+            lb_hash_header: String::with_capacity(0),
+            compress_min_size: String::from("256"),
+            compress_types: String::from(
+                "text/,application/json,application/javascript,application/xml,image/svg+xml"
+            ),
+            tls_cert: String::with_capacity(0),
+            tls_key:  String::with_capacity(0),
+            access_log:     String::with_capacity(0),
+            access_log_max: String::from("10m"),
+            breaker_fail_threshold: String::from("5"),
+            breaker_window:         String::from("30"),
+            breaker_cooldown:       String::from("10"),
+            breaker_max_cooldown:   String::from("300"),
+            shutdown_timeout: String::from("30"),
+            admin_token:      String::with_capacity(0),
         }
     }
 }
@@ -59,9 +97,10 @@ macro_rules! arg_err {
         concat!("参数 ", $text, " 格式错误")
     };
 }
+pub(crate) use arg_err;
 
 /// 获取当前时间基于UNIX_EPOCH的秒数
-fn unix_timestamp() -> u64 {
+pub(crate) fn unix_timestamp() -> u64 {
     SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs()
 }
 
@@ -116,11 +155,17 @@ fn main() {
     };
     log::info!("正在启动{}服务...", APP_NAME);
 
+    // controller接管配置的后续生命周期，全程通过controller::config()共享访问，避免与热重载竞争
+    let controller = controller::init(ac);
+    let ac = controller::config();
+
     let addr: std::net::SocketAddr = ac.listen.parse().unwrap();
 
+    proxy_log::init(&ac);
+
     let mut srv = HttpServer::new("", true);
     srv.default_handler(proxy::proxy_handler);
-    // srv.middleware(ProxyLog);
+    srv.middleware(proxy_log::ProxyLog);
 
     proxy::init_client(Some(Duration::from_secs(
         AppGlobal::get().connect_timeout as u64,
@@ -135,15 +180,39 @@ fn main() {
         "query/*": apis::query,
         "reg": apis::reg,
         "unreg": apis::unreg,
+        "shutdown": apis::admin_shutdown,
+        "reload": apis::admin_reload,
     );
 
-    let async_fn = async move {
-        // 运行http server主服务
-        srv.run(addr).await.context("http服务运行失败").unwrap();
+    // 同时配置了证书和私钥才启用TLS监听，否则沿用明文http
+    let tls_config = if !ac.tls_cert.is_empty() && !ac.tls_key.is_empty() {
+        Some(proxy::load_tls_config(&ac.tls_cert, &ac.tls_key).expect("加载TLS证书失败"))
+    } else {
+        None
     };
 
+    let shutdown_timeout = Duration::from_secs(
+        ac.shutdown_timeout.parse().expect(arg_err!("shutdown-timeout")),
+    );
+    srv.shutdown_signal(Box::pin(controller.wait_shutdown()), shutdown_timeout);
+
     let threads = ac.threads.parse::<usize>().expect(arg_err!("threads"));
 
+    let async_fn = async move {
+        // 后台存活检测任务：清理心跳超时及健康检查失败的端点
+        tokio::spawn(proxy::spawn_health_supervisor());
+        // 信号监听：SIGTERM/SIGINT触发优雅停机，SIGHUP(Unix)触发配置热重载
+        controller::spawn_signal_listener();
+
+        // 运行http(s) server主服务，收到停机信号后会停止接受新连接并等待在途请求完成
+        match tls_config {
+            Some(tls_config) => srv.run_tls(addr, tls_config).await.context("https服务运行失败").unwrap(),
+            None => srv.run(addr).await.context("http服务运行失败").unwrap(),
+        }
+
+        log::info!("{}服务已退出", APP_NAME);
+    };
+
     cfg_if::cfg_if! {
         if #[cfg(not(feature = "multi_thread"))] {
             assert!(threads == 1, "{APP_NAME}当前版本不支持多线程");
@@ -0,0 +1,780 @@
+//! 反向代理核心逻辑：服务注册表、请求转发与后台存活检测
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock, RwLock,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_compression::tokio::bufread::{BrotliEncoder, DeflateEncoder, GzipEncoder};
+use compact_str::CompactString;
+use futures_util::TryStreamExt;
+use httpserver::{HttpContext, HttpResult, Resp};
+use hyper::{
+    client::HttpConnector,
+    header::{self, HeaderValue},
+    Body, Client, Request, Response, Uri,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use rand::Rng;
+use serde::Serialize;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::{unix_timestamp, AppConf};
+
+/// 一致性哈希环上每个真实端点放置的虚拟节点数量
+const HASH_RING_VNODES: usize = 100;
+
+/// 试探请求(Trialing)最长占用时长，超时仍未揭晓结果则视为试探丢失，重新回到Open重新计时冷却，
+/// 避免端点因任务异常中断而被永久搁置
+const TRIAL_TIMEOUT_SECS: u64 = 30;
+
+/// 共享的http客户端类型，所有到上游的请求都复用此连接池，同时支持http/https上游
+type HttpClient = Client<HttpsConnector<HttpConnector>>;
+
+/// 熔断器状态：Closed正常放行，Open熔断拒绝，HalfOpen冷却结束等待派发试探请求，
+/// Trialing表示试探请求已被某次select()领取、结果揭晓前不再重复派发
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+    Trialing,
+}
+
+impl Default for BreakerState {
+    fn default() -> Self { Self::Closed }
+}
+
+/// 已注册的单个服务端点
+#[derive(Clone, Serialize)]
+pub struct Endpoint {
+    /// 端点地址，形如 http://host:port
+    pub url: CompactString,
+    /// 健康检查路径，为空表示不做主动探测，仅依赖心跳
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_path: Option<CompactString>,
+    /// 最后一次收到心跳(register_service调用)的时间戳
+    #[serde(skip)]
+    pub last_seen: u64,
+    /// 主动探测连续失败次数
+    #[serde(skip)]
+    pub fail_count: u32,
+    /// 熔断器当前状态
+    pub breaker: BreakerState,
+    /// 滑动窗口内的连续失败次数
+    #[serde(skip)]
+    consecutive_failures: u32,
+    /// 最近一次失败的时间戳，用于判断失败计数是否已滑出窗口
+    #[serde(skip)]
+    last_failure_at: u64,
+    /// 进入Open状态的时间戳
+    #[serde(skip)]
+    opened_at: u64,
+    /// 当前使用的冷却时长，失败会按配置指数增长
+    #[serde(skip)]
+    cooldown_secs: u64,
+    /// 进入Trialing状态(试探请求被领取)的时间戳，用于判断试探是否已超时丢失
+    #[serde(skip)]
+    trial_started_at: u64,
+}
+
+impl Endpoint {
+    fn new(url: CompactString, health_path: Option<CompactString>) -> Self {
+        Self {
+            url,
+            health_path,
+            last_seen: unix_timestamp(),
+            fail_count: 0,
+            breaker: BreakerState::Closed,
+            consecutive_failures: 0,
+            last_failure_at: 0,
+            opened_at: 0,
+            cooldown_secs: 0,
+            trial_started_at: 0,
+        }
+    }
+
+    /// 若端点处于Open状态且冷却已到期，转入HalfOpen等待被select()领取试探；
+    /// 若试探请求已被领取(Trialing)但长时间未揭晓结果，视为试探丢失，重新进入Open并重新计时冷却
+    fn refresh_breaker(&mut self, now: u64) {
+        if self.breaker == BreakerState::Open && now.saturating_sub(self.opened_at) >= self.cooldown_secs {
+            self.breaker = BreakerState::HalfOpen;
+        } else if self.breaker == BreakerState::Trialing
+            && now.saturating_sub(self.trial_started_at) >= TRIAL_TIMEOUT_SECS
+        {
+            self.breaker = BreakerState::Open;
+            self.opened_at = now;
+        }
+    }
+
+    /// 领取一次HalfOpen试探机会，占用期间不再被select()重复派发
+    fn claim_trial(&mut self, now: u64) {
+        self.breaker = BreakerState::Trialing;
+        self.trial_started_at = now;
+    }
+
+    /// 记录一次成功的上游调用结果
+    fn record_success(&mut self) {
+        self.breaker = BreakerState::Closed;
+        self.consecutive_failures = 0;
+        self.cooldown_secs = 0;
+    }
+
+    /// 记录一次失败的上游调用结果(连接错误/超时/5xx)，按熔断配置决定是否跳闸
+    fn record_failure(&mut self, cfg: &BreakerConfig) {
+        let now = unix_timestamp();
+        if now.saturating_sub(self.last_failure_at) > cfg.window_secs {
+            self.consecutive_failures = 0;
+        }
+        self.consecutive_failures += 1;
+        self.last_failure_at = now;
+
+        let should_open = match self.breaker {
+            BreakerState::HalfOpen | BreakerState::Trialing => true,
+            BreakerState::Open => false,
+            BreakerState::Closed => self.consecutive_failures >= cfg.fail_threshold,
+        };
+        if !should_open {
+            return;
+        }
+
+        self.cooldown_secs = if self.cooldown_secs == 0 {
+            cfg.base_cooldown_secs
+        } else {
+            (self.cooldown_secs * 2).min(cfg.max_cooldown_secs)
+        };
+        self.breaker = BreakerState::Open;
+        self.opened_at = now;
+    }
+}
+
+/// 熔断器相关的可配置参数
+#[derive(Clone, Copy)]
+pub struct BreakerConfig {
+    pub fail_threshold: u32,
+    pub window_secs: u64,
+    pub base_cooldown_secs: u64,
+    pub max_cooldown_secs: u64,
+}
+
+impl BreakerConfig {
+    fn from_app_conf(ac: &AppConf) -> Self {
+        Self {
+            fail_threshold: ac.breaker_fail_threshold.parse().expect(crate::arg_err!("breaker-fail-threshold")),
+            window_secs: ac.breaker_window.parse().expect(crate::arg_err!("breaker-window")),
+            base_cooldown_secs: ac.breaker_cooldown.parse().expect(crate::arg_err!("breaker-cooldown")),
+            max_cooldown_secs: ac.breaker_max_cooldown.parse().expect(crate::arg_err!("breaker-max-cooldown")),
+        }
+    }
+}
+
+/// 某一个路径下注册的全部服务端点
+#[derive(Clone, Serialize)]
+pub struct ServiceGroup {
+    pub path: CompactString,
+    pub endpoints: Vec<Endpoint>,
+    /// 轮询负载均衡使用的游标，使用Arc共享以便只读快照时无需加锁复制状态
+    #[serde(skip)]
+    rr_cursor: Arc<AtomicUsize>,
+    /// 一致性哈希环缓存，仅在端点集合(注册/注销/健康检查剔除)变化时失效，熔断状态的
+    /// 临时变化不会触发重建，从而保证"最小重映射"：端点集合不变时同一key始终落在同一节点上
+    #[serde(skip)]
+    hash_ring: Option<Arc<BTreeMap<u64, CompactString>>>,
+}
+
+impl ServiceGroup {
+    fn new(path: CompactString) -> Self {
+        Self {
+            path,
+            endpoints: Vec::new(),
+            rr_cursor: Arc::new(AtomicUsize::new(0)),
+            hash_ring: None,
+        }
+    }
+
+    /// 端点集合发生变化时使哈希环缓存失效，下次一致性哈希选择时重新构建
+    fn invalidate_hash_ring(&mut self) {
+        self.hash_ring = None;
+    }
+
+    /// 获取一致性哈希环，必要时按当前全部注册端点重建并缓存；熔断导致的临时不可用端点
+    /// 不会从环上移除，只在select()查找阶段被跳过
+    fn hash_ring(&mut self) -> Arc<BTreeMap<u64, CompactString>> {
+        if let Some(ring) = &self.hash_ring {
+            return ring.clone();
+        }
+        let mut ring = BTreeMap::new();
+        for endpoint in &self.endpoints {
+            for replica in 0..HASH_RING_VNODES {
+                let node_key = hash_u64(&format!("{}:{}", endpoint.url, replica));
+                ring.insert(node_key, endpoint.url.clone());
+            }
+        }
+        let ring = Arc::new(ring);
+        self.hash_ring = Some(ring.clone());
+        ring
+    }
+}
+
+/// 负载均衡策略
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LbStrategy {
+    /// 按注册顺序轮询
+    RoundRobin,
+    /// 随机选取
+    Random,
+    /// 一致性哈希，按请求key(客户端地址或指定header)粘性路由
+    ConsistentHash,
+}
+
+impl LbStrategy {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "random" => Self::Random,
+            "consistent-hash" => Self::ConsistentHash,
+            _ => Self::RoundRobin,
+        }
+    }
+}
+
+fn hash_u64(s: &str) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    s.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl ServiceGroup {
+    /// 根据负载均衡策略，从未被熔断器排除的端点中选取一个；Open状态的端点冷却到期后
+    /// 转入HalfOpen，被选中时立即领取为Trialing，在试探结果揭晓前不再被重复派发
+    fn select(&mut self, strategy: LbStrategy, key: &str) -> Option<CompactString> {
+        let now = unix_timestamp();
+        for endpoint in &mut self.endpoints {
+            endpoint.refresh_breaker(now);
+        }
+
+        let eligible: Vec<usize> = self.endpoints.iter().enumerate()
+            .filter(|(_, e)| matches!(e.breaker, BreakerState::Closed | BreakerState::HalfOpen))
+            .map(|(i, _)| i)
+            .collect();
+
+        let chosen = match eligible.len() {
+            0 => return None,
+            1 => eligible[0],
+            n => match strategy {
+                LbStrategy::RoundRobin => {
+                    eligible[self.rr_cursor.fetch_add(1, Ordering::Relaxed) % n]
+                }
+                LbStrategy::Random => eligible[rand::thread_rng().gen_range(0..n)],
+                LbStrategy::ConsistentHash => {
+                    let eligible_urls: HashSet<&CompactString> = eligible.iter()
+                        .map(|&i| &self.endpoints[i].url)
+                        .collect();
+                    let ring = self.hash_ring();
+                    let url = select_by_hash_ring(&ring, key, &eligible_urls)?;
+                    eligible.into_iter().find(|&i| self.endpoints[i].url == url)?
+                }
+            },
+        };
+
+        let endpoint = &mut self.endpoints[chosen];
+        if endpoint.breaker == BreakerState::HalfOpen {
+            endpoint.claim_trial(now);
+        }
+        Some(endpoint.url.clone())
+    }
+}
+
+/// 在缓存的一致性哈希环上顺时针查找第一个当前可用(eligible)的端点；环本身不因熔断
+/// 状态变化而重建，只在查找阶段跳过暂不可用的节点
+fn select_by_hash_ring(
+    ring: &BTreeMap<u64, CompactString>,
+    key: &str,
+    eligible: &HashSet<&CompactString>,
+) -> Option<CompactString> {
+    if ring.is_empty() {
+        return None;
+    }
+    let hash = hash_u64(key);
+    ring.range(hash..).chain(ring.iter())
+        .map(|(_, url)| url)
+        .find(|url| eligible.contains(url))
+        .cloned()
+}
+
+type Registry = HashMap<CompactString, ServiceGroup>;
+
+/// 全局服务注册表
+fn registry() -> &'static RwLock<Registry> {
+    static REG: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REG.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+tokio::task_local! {
+    /// 当前请求选中的上游端点，供ProxyLog等中间件在请求结束后读取
+    pub(crate) static CHOSEN_ENDPOINT: std::cell::RefCell<Option<CompactString>>;
+}
+
+/// 全局http客户端，由init_client初始化一次后全程复用
+static CLIENT: OnceLock<HttpClient> = OnceLock::new();
+
+fn client() -> &'static HttpClient {
+    CLIENT.get().expect("proxy::init_client未被调用")
+}
+
+/// 初始化共享的http客户端，connect_timeout应用到底层HttpConnector上控制建连超时。
+/// 连接器同时支持明文http和使用系统根证书的rustls https，按注册端点的scheme自动选择
+pub fn init_client(connect_timeout: Option<Duration>) {
+    CLIENT.get_or_init(|| {
+        let mut connector = HttpConnector::new();
+        connector.set_connect_timeout(connect_timeout);
+        connector.enforce_http(false);
+        let https = HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(connector);
+        Client::builder().build(https)
+    });
+}
+
+/// 从pem格式的证书/私钥文件构建rustls服务端配置，供TLS监听使用
+pub fn load_tls_config(cert_path: &str, key_path: &str) -> anyhow::Result<std::sync::Arc<rustls::ServerConfig>> {
+    use std::io::BufReader;
+
+    let cert_file = std::fs::File::open(cert_path)
+        .with_context(|| format!("打开证书文件失败: {cert_path}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .collect::<Result<Vec<_>, _>>()
+        .context("解析证书文件失败")?;
+
+    let key_file = std::fs::File::open(key_path)
+        .with_context(|| format!("打开私钥文件失败: {key_path}"))?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(key_file))
+        .context("解析私钥文件失败")?
+        .context("私钥文件中未找到有效私钥")?;
+
+    let config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("构建TLS服务端配置失败")?;
+
+    Ok(std::sync::Arc::new(config))
+}
+
+/// 注册服务(心跳复用同一接口)，返回true表示这是一个新端点，false表示仅刷新了心跳时间
+pub fn register_service(path: &str, endpoint: &str) -> bool {
+    register_service_with_health(path, endpoint, None)
+}
+
+/// 注册服务，可附带一个健康检查路径，用于后台主动探测
+pub fn register_service_with_health(
+    path: &str,
+    endpoint: &str,
+    health_path: Option<CompactString>,
+) -> bool {
+    let mut reg = registry().write().unwrap();
+    let group = reg.entry(CompactString::new(path))
+        .or_insert_with(|| ServiceGroup::new(CompactString::new(path)));
+
+    match group.endpoints.iter_mut().find(|e| e.url == endpoint) {
+        Some(e) => {
+            e.last_seen = unix_timestamp();
+            false
+        }
+        None => {
+            group.endpoints.push(Endpoint::new(CompactString::new(endpoint), health_path));
+            group.invalidate_hash_ring();
+            true
+        }
+    }
+}
+
+/// 取消服务注册
+pub fn unregister_service(path: &str, endpoint: &str) {
+    let mut reg = registry().write().unwrap();
+    if let Some(group) = reg.get_mut(path) {
+        let before = group.endpoints.len();
+        group.endpoints.retain(|e| e.url != endpoint);
+        if group.endpoints.len() != before {
+            group.invalidate_hash_ring();
+        }
+        if group.endpoints.is_empty() {
+            reg.remove(path);
+        }
+    }
+}
+
+/// 查询某路径下全部已注册端点地址(不区分熔断状态)，路径不存在返回None
+pub fn service_query(path: &str) -> Option<Vec<CompactString>> {
+    let reg = registry().read().unwrap();
+    reg.get(path).map(|g| g.endpoints.iter().map(|e| e.url.clone()).collect())
+}
+
+/// 返回当前注册表的完整快照，供status接口展示
+pub fn service_status() -> Vec<ServiceGroup> {
+    registry().read().unwrap().values().cloned().collect()
+}
+
+/// 按配置的负载均衡策略，从某路径下未被熔断的端点中选取一个用于转发
+fn select_endpoint(path: &str, strategy: LbStrategy, key: &str) -> Option<CompactString> {
+    let mut reg = registry().write().unwrap();
+    reg.get_mut(path)?.select(strategy, key)
+}
+
+/// 记录一次到指定端点的上游调用结果，驱动熔断器状态流转
+fn record_outcome(path: &str, url: &str, ok: bool, cfg: &BreakerConfig) {
+    let mut reg = registry().write().unwrap();
+    let Some(group) = reg.get_mut(path) else { return };
+    let Some(endpoint) = group.endpoints.iter_mut().find(|e| e.url == url) else { return };
+
+    if ok {
+        endpoint.record_success();
+    } else {
+        endpoint.record_failure(cfg);
+        if endpoint.breaker == BreakerState::Open {
+            log::warn!("endpoint[{}: {}]触发熔断，进入Open状态", path, url);
+        }
+    }
+}
+
+/// 计算一致性哈希使用的请求key：优先取配置的header，否则取客户端地址
+fn hash_key(ctx: &HttpContext, hash_header: &str) -> CompactString {
+    if !hash_header.is_empty() {
+        if let Some(value) = ctx.req.headers().get(hash_header).and_then(|v| v.to_str().ok()) {
+            return CompactString::new(value);
+        }
+    }
+    // 只取IP，不含临时分配的源端口，否则同一客户端每次新建连接都会落到不同的哈希桶上，
+    // 起不到粘性路由的作用
+    CompactString::new(ctx.addr.ip().to_string())
+}
+
+/// 反向代理默认处理函数：按路径查找端点并转发请求
+pub async fn proxy_handler(ctx: HttpContext) -> HttpResult {
+    let path = CompactString::new(ctx.req.uri().path());
+    let ac = crate::controller::config();
+    let strategy = LbStrategy::parse(&ac.lb_strategy);
+    let key = hash_key(&ctx, &ac.lb_hash_header);
+    let breaker_cfg = BreakerConfig::from_app_conf(&ac);
+    let accept_encoding = ctx.req.headers().get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_owned();
+
+    let endpoint = match select_endpoint(&path, strategy, &key) {
+        Some(e) => e,
+        None => return Resp::fail("no service available for path"),
+    };
+    let _ = CHOSEN_ENDPOINT.try_with(|cell| *cell.borrow_mut() = Some(endpoint.clone()));
+
+    let uri: Uri = match build_upstream_uri(&endpoint, &ctx.req) {
+        Ok(uri) => uri,
+        Err(_) => {
+            // select()可能刚把该端点从HalfOpen领取为Trialing，提前返回前必须回报结果，
+            // 否则端点会被白白搁置到TRIAL_TIMEOUT_SECS超时
+            record_outcome(&path, &endpoint, false, &breaker_cfg);
+            return Resp::fail("invalid upstream endpoint");
+        }
+    };
+
+    if is_upgrade_request(&ctx.req) {
+        return proxy_upgrade(ctx.req, uri, path, endpoint, breaker_cfg).await;
+    }
+
+    let mut upstream_req = Request::builder()
+        .method(ctx.req.method().clone())
+        .uri(uri);
+    for (name, value) in ctx.req.headers() {
+        upstream_req = upstream_req.header(name, value);
+    }
+    let upstream_req = match upstream_req.body(Body::from(ctx.req.into_body())) {
+        Ok(req) => req,
+        Err(e) => {
+            record_outcome(&path, &endpoint, false, &breaker_cfg);
+            return Err(anyhow::anyhow!(e));
+        }
+    };
+
+    let resp = client().request(upstream_req).await;
+    let resp = match resp {
+        Ok(resp) => {
+            record_outcome(&path, &endpoint, !resp.status().is_server_error(), &breaker_cfg);
+            resp
+        }
+        Err(e) => {
+            record_outcome(&path, &endpoint, false, &breaker_cfg);
+            return Err(anyhow::anyhow!(e));
+        }
+    };
+    Ok(maybe_compress(resp, &accept_encoding, &ac))
+}
+
+/// 请求是否要求协议升级(如WebSocket握手)
+fn is_upgrade_request(req: &Request<Body>) -> bool {
+    let has_token = |name: &header::HeaderName, token: &str| {
+        req.headers().get(name)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.split(',').any(|t| t.trim().eq_ignore_ascii_case(token)))
+    };
+    has_token(&header::CONNECTION, "upgrade") && req.headers().contains_key(header::UPGRADE)
+}
+
+/// 将Upgrade请求(主要是WebSocket)透明转发给上游：先完成握手，再在两条连接间双向拼接字节流
+async fn proxy_upgrade(
+    mut req: Request<Body>,
+    uri: Uri,
+    path: CompactString,
+    endpoint: CompactString,
+    breaker_cfg: BreakerConfig,
+) -> HttpResult {
+    let mut upstream_req = Request::builder()
+        .method(req.method().clone())
+        .uri(uri);
+    for (name, value) in req.headers() {
+        upstream_req = upstream_req.header(name, value);
+    }
+    let upstream_req = match upstream_req.body(Body::empty()) {
+        Ok(req) => req,
+        Err(e) => {
+            record_outcome(&path, &endpoint, false, &breaker_cfg);
+            return Err(anyhow::anyhow!(e));
+        }
+    };
+
+    let mut upstream_resp = match client().request(upstream_req).await {
+        Ok(resp) => resp,
+        Err(e) => {
+            record_outcome(&path, &endpoint, false, &breaker_cfg);
+            return Err(anyhow::anyhow!(e));
+        }
+    };
+    record_outcome(&path, &endpoint, !upstream_resp.status().is_server_error(), &breaker_cfg);
+
+    if upstream_resp.status() != hyper::StatusCode::SWITCHING_PROTOCOLS {
+        return Ok(upstream_resp);
+    }
+
+    let mut client_resp = Response::builder().status(upstream_resp.status());
+    for (name, value) in upstream_resp.headers() {
+        client_resp = client_resp.header(name, value);
+    }
+    let client_resp = client_resp.body(Body::empty()).map_err(|e| anyhow::anyhow!(e))?;
+
+    tokio::spawn(async move {
+        let (client_upgraded, upstream_upgraded) = tokio::join!(
+            hyper::upgrade::on(&mut req),
+            hyper::upgrade::on(&mut upstream_resp),
+        );
+        match (client_upgraded, upstream_upgraded) {
+            (Ok(mut client_io), Ok(mut upstream_io)) => {
+                if let Err(e) = tokio::io::copy_bidirectional(&mut client_io, &mut upstream_io).await {
+                    log::warn!("websocket隧道异常结束: {e}");
+                }
+            }
+            _ => log::warn!("websocket握手升级失败，无法建立隧道"),
+        }
+    });
+
+    Ok(client_resp)
+}
+
+/// 客户端可接受的编码方式，按压缩率从高到低协商
+#[derive(Clone, Copy)]
+enum Encoding {
+    Br,
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Br => "br",
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+}
+
+/// 按Accept-Encoding逐个token解析q值协商编码：q=0表示客户端显式拒绝该编码；
+/// q值相同时按压缩率从高到低(br > gzip > deflate)选取
+fn negotiate_encoding(accept_encoding: &str) -> Option<Encoding> {
+    fn rank(e: Encoding) -> u8 {
+        match e {
+            Encoding::Br => 0,
+            Encoding::Gzip => 1,
+            Encoding::Deflate => 2,
+        }
+    }
+
+    let mut best: Option<(Encoding, f32)> = None;
+    for token in accept_encoding.split(',') {
+        let mut parts = token.split(';');
+        let encoding = match parts.next().unwrap_or("").trim() {
+            "br" => Encoding::Br,
+            "gzip" => Encoding::Gzip,
+            "deflate" => Encoding::Deflate,
+            _ => continue,
+        };
+
+        let q: f32 = parts
+            .find_map(|p| p.trim().strip_prefix("q="))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1.0);
+        if q <= 0.0 {
+            continue;
+        }
+
+        best = match best {
+            Some((cur, cur_q)) if cur_q > q || (cur_q == q && rank(cur) <= rank(encoding)) => Some((cur, cur_q)),
+            _ => Some((encoding, q)),
+        };
+    }
+    best.map(|(e, _)| e)
+}
+
+/// 按Accept-Encoding和配置的阈值/类型白名单，对可压缩的响应体做流式压缩
+fn maybe_compress(resp: Response<Body>, accept_encoding: &str, ac: &AppConf) -> Response<Body> {
+    if accept_encoding.is_empty() || resp.headers().contains_key(header::CONTENT_ENCODING) {
+        return resp;
+    }
+
+    let content_type = resp.headers().get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let compressible = ac.compress_types.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .any(|t| content_type.starts_with(t));
+    if !compressible {
+        return resp;
+    }
+
+    let min_size: u64 = ac.compress_min_size.parse().unwrap_or(0);
+    if let Some(len) = resp.headers().get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+    {
+        if len < min_size {
+            return resp;
+        }
+    }
+
+    let Some(encoding) = negotiate_encoding(accept_encoding) else { return resp };
+
+    let (mut parts, body) = resp.into_parts();
+    let reader = StreamReader::new(body.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)));
+    let body = match encoding {
+        Encoding::Br => Body::wrap_stream(ReaderStream::new(BrotliEncoder::new(reader))),
+        Encoding::Gzip => Body::wrap_stream(ReaderStream::new(GzipEncoder::new(reader))),
+        Encoding::Deflate => Body::wrap_stream(ReaderStream::new(DeflateEncoder::new(reader))),
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding.name()));
+    Response::from_parts(parts, body)
+}
+
+fn build_upstream_uri(endpoint: &str, req: &Request<Body>) -> Result<Uri, http::uri::InvalidUri> {
+    let base = endpoint.trim_end_matches('/');
+    let pq = req.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    format!("{base}{pq}").parse()
+}
+
+/// 后台存活检测任务：定期清理过期端点，并对声明了健康检查路径的端点做主动探测
+pub async fn spawn_health_supervisor() {
+    let ac = crate::controller::config();
+    let interval_secs = ac.health_check_interval.parse::<u64>()
+        .expect("参数 health-check-interval 格式错误")
+        .max(1);
+    let ttl_secs = ac.heartbeat_ttl.parse::<u64>()
+        .expect("参数 heartbeat-ttl 格式错误");
+    let max_fail = ac.health_check_fail_max.parse::<u32>()
+        .expect("参数 health-check-fail-max 格式错误")
+        .max(1);
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+    loop {
+        ticker.tick().await;
+        sweep_expired(ttl_secs);
+        probe_endpoints(max_fail).await;
+    }
+}
+
+/// 剔除超过ttl未刷新心跳的端点
+fn sweep_expired(ttl_secs: u64) {
+    if ttl_secs == 0 {
+        return;
+    }
+    let now = unix_timestamp();
+    let mut reg = registry().write().unwrap();
+    reg.retain(|_, group| {
+        let before = group.endpoints.len();
+        group.endpoints.retain(|e| {
+            let alive = now.saturating_sub(e.last_seen) <= ttl_secs;
+            if !alive {
+                log::warn!("endpoint[{}: {}]心跳超时，已剔除", group.path, e.url);
+            }
+            alive
+        });
+        if group.endpoints.len() != before {
+            group.invalidate_hash_ring();
+        }
+        !group.endpoints.is_empty()
+    });
+}
+
+/// 对声明了健康检查路径的端点发起主动探测
+async fn probe_endpoints(max_fail: u32) {
+    let targets: Vec<(CompactString, CompactString, CompactString)> = {
+        let reg = registry().read().unwrap();
+        reg.values()
+            .flat_map(|g| g.endpoints.iter().filter_map(|e| {
+                e.health_path.as_ref().map(|hp| (g.path.clone(), e.url.clone(), hp.clone()))
+            }))
+            .collect()
+    };
+
+    for (path, url, health_path) in targets {
+        let ok = probe_once(&url, &health_path).await;
+        let mut reg = registry().write().unwrap();
+        let Some(group) = reg.get_mut(path.as_str()) else { continue };
+        let Some(endpoint) = group.endpoints.iter_mut().find(|e| e.url == url) else { continue };
+
+        if ok {
+            endpoint.fail_count = 0;
+        } else {
+            endpoint.fail_count += 1;
+            log::warn!("endpoint[{}: {}]健康检查失败({}次)", path, url, endpoint.fail_count);
+            if endpoint.fail_count >= max_fail {
+                log::warn!("endpoint[{}: {}]健康检查连续失败达到上限，已剔除", path, url);
+                group.endpoints.retain(|e| e.url != url);
+                group.invalidate_hash_ring();
+            }
+        }
+        if group.endpoints.is_empty() {
+            reg.remove(path.as_str());
+        }
+    }
+}
+
+async fn probe_once(endpoint: &str, health_path: &str) -> bool {
+    let base = endpoint.trim_end_matches('/');
+    let hp = health_path.trim_start_matches('/');
+    let uri: Uri = match format!("{base}/{hp}").parse() {
+        Ok(uri) => uri,
+        Err(_) => return false,
+    };
+
+    match client().get(uri).await {
+        Ok(resp) => resp.status().is_success(),
+        Err(_) => false,
+    }
+}
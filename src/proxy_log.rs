@@ -0,0 +1,189 @@
+//! 请求访问日志中间件：记录每次代理请求的客户端地址、方法、路径、
+//! 所选上游端点、响应状态码、字节数与耗时
+
+use std::{
+    io::Write,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context as TaskContext, Poll},
+    time::Instant,
+};
+
+use compact_str::{format_compact, CompactString};
+use futures_util::Stream;
+use httpserver::{HttpContext, HttpResult, Middleware, Next};
+use hyper::body::Bytes;
+use localtime::LocalTime;
+
+use crate::{proxy, AppConf};
+
+/// 访问日志中间件，对应main中原本注释掉的`srv.middleware(ProxyLog)`
+pub struct ProxyLog;
+
+#[async_trait::async_trait]
+impl Middleware for ProxyLog {
+    async fn handle(&self, ctx: HttpContext, next: Next<'_>) -> HttpResult {
+        let start = Instant::now();
+        let addr = ctx.addr;
+        let method = ctx.req.method().clone();
+        let path = CompactString::new(ctx.req.uri().path());
+
+        let mut endpoint = None;
+        let result = proxy::CHOSEN_ENDPOINT.scope(std::cell::RefCell::new(None), async {
+            let resp = next.run(ctx).await;
+            endpoint = proxy::CHOSEN_ENDPOINT.with(|c| c.borrow().clone());
+            resp
+        }).await;
+
+        let endpoint = CompactString::from(endpoint.as_deref().unwrap_or("-"));
+
+        // 错误响应没有实际下发的响应体，直接按原有方式记录
+        let mut resp = match result {
+            Ok(resp) => resp,
+            Err(e) => {
+                let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+                write_access_line(addr, &method, &path, 500, 0, elapsed_ms, &endpoint);
+                return Err(e);
+            }
+        };
+
+        // maybe_compress对压缩后的响应体会移除Content-Length头，此时响应头上已经拿不到真实字节数，
+        // 只能用统计流包装响应体，等body真正下发完(或连接提前断开被drop)后再按实际字节数落盘日志
+        let status = resp.status().as_u16();
+        let (parts, body) = resp.into_parts();
+        let counted = CountingBody::new(body, move |bytes| {
+            let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+            write_access_line(addr, &method, &path, status, bytes, elapsed_ms, &endpoint);
+        });
+        resp = hyper::Response::from_parts(parts, hyper::Body::wrap_stream(counted));
+
+        Ok(resp)
+    }
+}
+
+fn write_access_line(
+    addr: std::net::SocketAddr,
+    method: &hyper::Method,
+    path: &str,
+    status: u16,
+    bytes: u64,
+    elapsed_ms: f64,
+    endpoint: &str,
+) {
+    // Combined Log Format风格的单行记录
+    write_line(&format_compact!(
+        "{} - - [{}] \"{} {}\" {} {} {:.3} {}",
+        addr,
+        LocalTime::now(),
+        method,
+        path,
+        status,
+        bytes,
+        elapsed_ms,
+        endpoint,
+    ));
+}
+
+/// 包装响应体的字节统计流：压缩后的响应体不再带Content-Length头，只能在流式转发过程中
+/// 累加实际经过的字节数；on_finish在流自然结束或(客户端提前断开导致)被提前drop时都会触发一次
+struct CountingBody {
+    inner: hyper::Body,
+    count: u64,
+    on_finish: Option<Box<dyn FnOnce(u64) + Send>>,
+}
+
+impl CountingBody {
+    fn new(inner: hyper::Body, on_finish: impl FnOnce(u64) + Send + 'static) -> Self {
+        Self { inner, count: 0, on_finish: Some(Box::new(on_finish)) }
+    }
+
+    fn finish(&mut self) {
+        if let Some(on_finish) = self.on_finish.take() {
+            on_finish(self.count);
+        }
+    }
+}
+
+impl Stream for CountingBody {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                this.count += chunk.len() as u64;
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e))),
+            Poll::Ready(None) => {
+                this.finish();
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for CountingBody {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+struct AccessLogFile {
+    path: CompactString,
+    max_size: u64,
+    file: std::fs::File,
+}
+
+impl AccessLogFile {
+    fn write_line(&mut self, line: &str) {
+        let _ = writeln!(self.file, "{line}");
+        if self.max_size == 0 {
+            return;
+        }
+        if self.file.metadata().map(|m| m.len()).unwrap_or(0) < self.max_size {
+            return;
+        }
+        let rotated = format_compact!("{}.old", self.path);
+        let _ = std::fs::rename(self.path.as_str(), rotated.as_str());
+        if let Ok(file) = std::fs::OpenOptions::new().create(true).append(true).open(self.path.as_str()) {
+            self.file = file;
+        }
+    }
+}
+
+static ACCESS_LOG: std::sync::OnceLock<Option<Mutex<AccessLogFile>>> = std::sync::OnceLock::new();
+
+/// 初始化访问日志：若配置了access-log则打开独立的滚动日志文件，否则访问日志并入主日志(asynclog)输出
+pub fn init(ac: &AppConf) {
+    ACCESS_LOG.get_or_init(|| {
+        if ac.access_log.is_empty() {
+            return None;
+        }
+
+        let max_size = asynclog::parse_size(&ac.access_log_max)
+            .expect("参数 access-log-max 格式错误");
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&ac.access_log)
+            .map(|file| Mutex::new(AccessLogFile {
+                path: CompactString::new(&ac.access_log),
+                max_size,
+                file,
+            }))
+            .ok()
+    });
+}
+
+fn write_line(line: &str) {
+    match ACCESS_LOG.get() {
+        Some(Some(file)) => {
+            if let Ok(mut file) = file.lock() {
+                file.write_line(line);
+            }
+        }
+        _ => log::info!(target: "access", "{line}"),
+    }
+}